@@ -0,0 +1,283 @@
+use chrono::prelude::*;
+use log::info;
+use num_bigint::BigUint;
+
+use crate::model::block::{ Block, BlockId, Header, Nonce, Timestamp };
+use crate::model::hash::Hash;
+use crate::model::transaction::Transaction;
+
+const GENESIS_HASH: &str =
+    "0000f816a87f806bb0073dcf026a64fb40c946b5abee2573702828694d5b4c43";
+const GENESIS_PREVIOUS_HASH: &str = "GENESIS!";
+
+/// Chain-structure rules (linkage, ids, transaction validity, content hashing) live on
+/// `Block` itself; an `Engine` only owns the consensus rules layered on top of that: how a
+/// new block is sealed, and how an existing seal is checked. This mirrors how chain clients
+/// like OpenEthereum keep Ethash/NullEngine swappable behind one `Engine` trait while the
+/// block structure stays the same either way.
+pub trait Engine: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    fn genesis(&self) -> Block;
+
+    /// Builds and seals a new block on top of `previous_blocks`.
+    fn seal(&self, previous_blocks: &[Block], data: Vec<Transaction>) -> Block;
+
+    /// Checks that `block`'s seal is valid given the chain before it. Assumes
+    /// `block.is_valid_structure(previous_blocks)` has already been checked by the caller.
+    fn verify_seal(&self, block: &Block, previous_blocks: &[Block]) -> Result<(), String>;
+}
+
+/// Looks up an engine by the name a node was started with (`--engine pow`, `--engine null`).
+/// Unknown names fall back to proof-of-work, since that's the chain's historical default.
+pub fn engine_by_name(name: &str) -> Box<dyn Engine> {
+    match name {
+        "null" => Box::new(NullEngine),
+        _ => Box::new(PowEngine),
+    }
+}
+
+/// SHA-256 proof-of-work engine. Every `RETARGET_INTERVAL` blocks the numeric target is
+/// retargeted towards `TARGET_BLOCK_TIME_SECS`.
+pub struct PowEngine;
+
+const RETARGET_INTERVAL: u64 = 10;
+const TARGET_BLOCK_TIME_SECS: i64 = 10;
+/// A retarget can at most quadruple or quarter the previous target in one step.
+const MAX_RETARGET_FACTOR: u32 = 4;
+
+impl PowEngine {
+    /// The easiest target the chain will ever accept, equivalent to the legacy fixed "0000"
+    /// hash-prefix rule. Also the starting target used at genesis.
+    fn max_target() -> BigUint {
+        (BigUint::from(1u8) << (256 - 16)) - BigUint::from(1u8)
+    }
+
+    /// The numeric target a block at `new_id` must meet, derived from the chain so far.
+    fn expected_target(&self, previous_blocks: &[Block], new_id: BlockId) -> BigUint {
+        if new_id == 0 {
+            return PowEngine::max_target();
+        }
+
+        let parent = previous_blocks.last().expect("chain has at least genesis");
+        let parent_target = parent.header.target.to_biguint();
+        let retarget_window = RETARGET_INTERVAL as usize;
+
+        if new_id % RETARGET_INTERVAL != 0 || previous_blocks.len() < retarget_window {
+            return parent_target;
+        }
+
+        let window_first = &previous_blocks[previous_blocks.len() - retarget_window];
+        let actual = (parent.header.timestamp - window_first.header.timestamp).max(1) as u64;
+        let expected = (RETARGET_INTERVAL as i64 * TARGET_BLOCK_TIME_SECS) as u64;
+
+        let new_target = (&parent_target * actual) / expected;
+
+        let min_target = &parent_target / MAX_RETARGET_FACTOR;
+        let max_target = &parent_target * MAX_RETARGET_FACTOR;
+        let new_target = new_target.clamp(min_target, max_target);
+
+        new_target.min(PowEngine::max_target())
+    }
+
+    fn mine(
+        &self,
+        id: BlockId,
+        timestamp: Timestamp,
+        previous_hash: &Hash,
+        target_hash: &Hash,
+        data: &[Transaction],
+        target: &BigUint
+    ) -> (Nonce, Hash) {
+        info!("mining block...");
+        let mut nonce = 0;
+
+        loop {
+            if nonce % 100000 == 0 {
+                info!("nonce: {}", nonce);
+            }
+
+            let hash = Block::calculate_hash(id, timestamp, previous_hash, target_hash, data, nonce);
+
+            if hash.meets_target(target) {
+                info!("mined! nonce: {}, hash: {}", nonce, hash);
+                return (nonce, hash);
+            }
+
+            nonce += 1;
+        }
+    }
+}
+
+impl Engine for PowEngine {
+    fn name(&self) -> &'static str {
+        "pow"
+    }
+
+    fn genesis(&self) -> Block {
+        Block::assemble(
+            Header::new(
+                0,
+                Utc::now().timestamp(),
+                0,
+                Hash::wrap(String::from(GENESIS_HASH)),
+                Hash::wrap(String::from(GENESIS_PREVIOUS_HASH)),
+                Hash::from_biguint(&PowEngine::max_target())
+            ),
+            vec![]
+        )
+    }
+
+    fn seal(&self, previous_blocks: &[Block], data: Vec<Transaction>) -> Block {
+        let previous_block = previous_blocks.last().expect("chain has at least genesis");
+        let id = previous_block.header.id + 1;
+        let timestamp = Utc::now().timestamp();
+        let target = self.expected_target(previous_blocks, id);
+        let target_hash = Hash::from_biguint(&target);
+        let (nonce, hash) = self.mine(
+            id,
+            timestamp,
+            &previous_block.header.hash,
+            &target_hash,
+            &data,
+            &target
+        );
+
+        Block::assemble(
+            Header::new(id, timestamp, nonce, hash, previous_block.header.hash.clone(), target_hash),
+            data
+        )
+    }
+
+    fn verify_seal(&self, block: &Block, previous_blocks: &[Block]) -> Result<(), String> {
+        let expected_target = self.expected_target(previous_blocks, block.header.id);
+
+        if block.header.target.to_biguint() != expected_target {
+            return Err(String::from("block difficulty does not match the expected retarget"));
+        }
+
+        if !block.header.hash.meets_target(&expected_target) {
+            return Err(String::from("block hash does not meet its target"));
+        }
+
+        Ok(())
+    }
+}
+
+/// Accepts any well-formed block without mining. Useful for local testing and CI, where
+/// waiting on real proof-of-work just burns time.
+pub struct NullEngine;
+
+impl Engine for NullEngine {
+    fn name(&self) -> &'static str {
+        "null"
+    }
+
+    fn genesis(&self) -> Block {
+        Block::assemble(
+            Header::new(
+                0,
+                Utc::now().timestamp(),
+                0,
+                Hash::wrap(String::from(GENESIS_HASH)),
+                Hash::wrap(String::from(GENESIS_PREVIOUS_HASH)),
+                Hash::wrap(String::from("0"))
+            ),
+            vec![]
+        )
+    }
+
+    fn seal(&self, previous_blocks: &[Block], data: Vec<Transaction>) -> Block {
+        let previous_block = previous_blocks.last().expect("chain has at least genesis");
+        let id = previous_block.header.id + 1;
+        let timestamp = Utc::now().timestamp();
+        let target_hash = Hash::wrap(String::from("0"));
+        let hash = Block::calculate_hash(
+            id,
+            timestamp,
+            &previous_block.header.hash,
+            &target_hash,
+            &data,
+            0
+        );
+
+        Block::assemble(
+            Header::new(id, timestamp, 0, hash, previous_block.header.hash.clone(), target_hash),
+            data
+        )
+    }
+
+    fn verify_seal(&self, _block: &Block, _previous_blocks: &[Block]) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block_with(id: BlockId, timestamp: Timestamp, target: &BigUint) -> Block {
+        Block::assemble(
+            Header::new(
+                id,
+                timestamp,
+                0,
+                Hash::wrap(String::from("h")),
+                Hash::wrap(String::from("p")),
+                Hash::from_biguint(target)
+            ),
+            vec![]
+        )
+    }
+
+    #[test]
+    fn expected_target_holds_steady_when_not_a_retarget_block() {
+        let engine = PowEngine;
+        let parent_target = PowEngine::max_target() / 2u32;
+        let chain = vec![block_with(9, 0, &parent_target)];
+
+        assert_eq!(engine.expected_target(&chain, 11), parent_target);
+    }
+
+    #[test]
+    fn expected_target_clamps_to_quarter_when_blocks_come_in_too_fast() {
+        let engine = PowEngine;
+        let parent_target = PowEngine::max_target() / 2u32;
+        let window_first = block_with(0, 0, &parent_target);
+        let mut chain: Vec<Block> = (1..9).map(|id| block_with(id, 0, &parent_target)).collect();
+        chain.insert(0, window_first);
+        // Parent arrives 1s after the window's first block, far under the 100s expectation.
+        chain.push(block_with(9, 1, &parent_target));
+
+        let expected_min = &parent_target / MAX_RETARGET_FACTOR;
+
+        assert_eq!(engine.expected_target(&chain, 10), expected_min);
+    }
+
+    #[test]
+    fn expected_target_clamps_to_quadruple_when_blocks_come_in_too_slow() {
+        let engine = PowEngine;
+        let parent_target = PowEngine::max_target() / 8u32;
+        let window_first = block_with(0, 0, &parent_target);
+        let mut chain: Vec<Block> = (1..9).map(|id| block_with(id, 0, &parent_target)).collect();
+        chain.insert(0, window_first);
+        // Parent arrives 10x slower than the 100s expectation.
+        chain.push(block_with(9, 1000, &parent_target));
+
+        let expected_max = &parent_target * MAX_RETARGET_FACTOR;
+
+        assert_eq!(engine.expected_target(&chain, 10), expected_max);
+    }
+
+    #[test]
+    fn expected_target_never_exceeds_max_target() {
+        let engine = PowEngine;
+        let parent_target = PowEngine::max_target();
+        let window_first = block_with(0, 0, &parent_target);
+        let mut chain: Vec<Block> = (1..9).map(|id| block_with(id, 0, &parent_target)).collect();
+        chain.insert(0, window_first);
+        chain.push(block_with(9, 1000, &parent_target));
+
+        assert_eq!(engine.expected_target(&chain, 10), PowEngine::max_target());
+    }
+}
@@ -2,23 +2,77 @@ use libp2p::{
     core::upgrade,
     futures::StreamExt,
     mplex,
+    multiaddr::Protocol,
     noise::{ Keypair, NoiseConfig, X25519Spec },
-    swarm::{ Swarm, SwarmBuilder },
+    swarm::{ Swarm, SwarmBuilder, SwarmEvent },
     tcp::TokioTcpConfig,
+    Multiaddr,
+    PeerId,
     Transport,
 };
 use log::{ error, info };
 use std::time::Duration;
 use tokio::{ io::{ stdin, AsyncBufReadExt, BufReader }, select, spawn, sync::mpsc, time::sleep };
 
+mod chain_verify;
+mod engine;
 mod p2p;
+mod storage;
 mod model {
     pub mod block;
     pub mod blockchain;
     pub mod hash;
+    pub mod transaction;
 }
 
-use crate::{ model::blockchain::Blockchain, p2p::{ BlockchainBehaviour, ChainResponse } };
+use crate::{
+    model::blockchain::Blockchain,
+    p2p::{ BlockchainBehaviour, ChainResponse, WireMessage },
+};
+
+/// Picks the consensus engine a node runs: `--engine <name>` wins, falling back to the
+/// `ENGINE` environment variable, defaulting to proof-of-work.
+fn engine_name_from_args() -> String {
+    let mut args = std::env::args();
+
+    while let Some(arg) = args.next() {
+        if arg == "--engine" {
+            if let Some(name) = args.next() {
+                return name;
+            }
+        }
+    }
+
+    std::env::var("ENGINE").unwrap_or_else(|_| String::from("pow"))
+}
+
+/// Reads `--rendezvous <multiaddr>` (the multiaddr must end in `/p2p/<peer id>`) and
+/// `--rendezvous-server` from the CLI args. A node started with `--rendezvous-server` is the
+/// rendezvous point itself, so it has no point of its own to register or discover through.
+fn rendezvous_config_from_args() -> (Option<(PeerId, Multiaddr)>, bool) {
+    let args: Vec<String> = std::env::args().collect();
+    let is_rendezvous_server = args.iter().any(|arg| arg == "--rendezvous-server");
+
+    if is_rendezvous_server {
+        return (None, true);
+    }
+
+    let rendezvous_point = args
+        .iter()
+        .position(|arg| arg == "--rendezvous")
+        .and_then(|i| args.get(i + 1))
+        .and_then(|addr| addr.parse::<Multiaddr>().ok())
+        .and_then(|addr| {
+            let peer_id = addr.iter().find_map(|protocol| match protocol {
+                Protocol::P2p(hash) => PeerId::from_multihash(hash).ok(),
+                _ => None,
+            })?;
+
+            Some((peer_id, addr))
+        });
+
+    (rendezvous_point, false)
+}
 
 #[tokio::main]
 async fn main() {
@@ -26,6 +80,15 @@ async fn main() {
 
     info!("Peer Id: {}", p2p::PEER_ID.clone());
 
+    let engine = engine::engine_by_name(&engine_name_from_args());
+    info!("Consensus engine: {}", engine.name());
+
+    let (rendezvous_point, is_rendezvous_server) = rendezvous_config_from_args();
+
+    if is_rendezvous_server {
+        info!("Running as the rendezvous point for this chain");
+    }
+
     let (response_sender, mut response_receiver) = mpsc::unbounded_channel();
     let (init_sender, mut init_receiver) = mpsc::unbounded_channel();
 
@@ -41,9 +104,10 @@ async fn main() {
         .boxed();
 
     let behaviour = p2p::BlockchainBehaviour::new(
-        Blockchain::new(),
+        Blockchain::new(engine),
         response_sender,
-        init_sender.clone()
+        init_sender.clone(),
+        rendezvous_point
     ).await;
 
     let mut swarm = SwarmBuilder::new(transport, behaviour, *p2p::PEER_ID)
@@ -55,6 +119,7 @@ async fn main() {
         .build();
 
     let mut stdin = BufReader::new(stdin()).lines();
+    let mut rendezvous_discovery = tokio::time::interval(Duration::from_secs(30));
 
     Swarm::listen_on(
         &mut swarm,
@@ -81,7 +146,16 @@ async fn main() {
                 _init = init_receiver.recv() => {
                     Some(p2p::EventType::Init)
                 }
-                _event = swarm.select_next_some() => {
+                _tick = rendezvous_discovery.tick() => {
+                    if swarm.behaviour().rendezvous_point.is_some() {
+                        p2p::discover_via_rendezvous(&mut swarm);
+                    }
+                    None
+                }
+                event = swarm.select_next_some() => {
+                    if let SwarmEvent::ConnectionEstablished { peer_id, .. } = event {
+                        p2p::register_on_connection(&mut swarm, peer_id);
+                    }
                     None
                 },
             }
@@ -101,32 +175,51 @@ async fn main() {
     }
 
     fn handle_init_event(swarm: &mut Swarm<BlockchainBehaviour>) {
+        p2p::dial_rendezvous_point(swarm);
+
         let peers = p2p::get_list_of_peers(swarm);
-        swarm.behaviour_mut().blockchain = swarm.behaviour_mut().blockchain.genesis();
+
+        if swarm.behaviour().blockchain.is_empty() {
+            info!("No persisted chain found, creating genesis block");
+            swarm.behaviour_mut().blockchain.genesis();
+        } else {
+            info!("Resuming from persisted chain at block {}", swarm.behaviour().blockchain.blocks.last().unwrap().header.id);
+        }
 
         info!("Connected nodes: {}", peers.len());
 
         if let Some(last_peer) = peers.last() {
-            let req = p2p::LocalChainRequest {
+            let wire_message = WireMessage::Request(p2p::LocalChainRequest {
                 from_peer_id: last_peer.to_string(),
-            };
+            });
 
-            let json = serde_json::to_string(&req).expect("can jsonify request");
+            let publish_result = swarm
+                .behaviour_mut()
+                .gossipsub.publish(p2p::CHAIN_TOPIC.clone(), wire_message.to_bytes());
 
-            swarm.behaviour_mut().floodsub.publish(p2p::CHAIN_TOPIC.clone(), json.as_bytes());
+            if let Err(e) = publish_result {
+                error!("Failed to publish chain request: {:?}", e);
+            }
         }
     }
 
     fn handle_local_chain_response(resp: ChainResponse, swarm: &mut Swarm<BlockchainBehaviour>) {
-        let json = serde_json::to_string(&resp).expect("can jsonify response");
+        let wire_message = WireMessage::ChainResponse(resp);
+
+        let publish_result = swarm
+            .behaviour_mut()
+            .gossipsub.publish(p2p::CHAIN_TOPIC.clone(), wire_message.to_bytes());
 
-        swarm.behaviour_mut().floodsub.publish(p2p::CHAIN_TOPIC.clone(), json.as_bytes());
+        if let Err(e) = publish_result {
+            error!("Failed to publish chain response: {:?}", e);
+        }
     }
 
     fn handle_input_event(line: String, swarm: &mut Swarm<BlockchainBehaviour>) {
         match line.as_str() {
             "ls p" => p2p::handle_print_peers(swarm),
             "ls c" => p2p::handle_print_chain(swarm),
+            "list peers" => p2p::handle_list_rendezvous_peers(swarm),
             cmd if cmd.starts_with("create block") => p2p::handle_create_block(cmd, swarm),
             _ => error!("unknown command"),
         }
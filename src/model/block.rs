@@ -1,10 +1,9 @@
-use log::info;
+use std::collections::HashMap;
+
 use serde::{ Deserialize, Serialize };
-use chrono::prelude::*;
 
 use super::hash::Hash;
-
-const DIFFICULTY: &'static str = "0000";
+use super::transaction::Transaction;
 
 pub type BlockId = u64;
 pub type Nonce = u64;
@@ -17,15 +16,17 @@ pub struct Header {
     pub nonce: Nonce,
     pub hash: Hash,
     pub previous_hash: Hash,
+    pub target: Hash,
 }
 
 impl Header {
-    fn new(
+    pub(crate) fn new(
         id: BlockId,
         timestamp: Timestamp,
         nonce: Nonce,
         hash: Hash,
-        previous_hash: Hash
+        previous_hash: Hash,
+        target: Hash
     ) -> Self {
         Header {
             id,
@@ -33,6 +34,7 @@ impl Header {
             nonce,
             hash,
             previous_hash,
+            target,
         }
     }
 }
@@ -40,41 +42,34 @@ impl Header {
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Block {
     pub header: Header,
-    pub data: String,
+    pub data: Vec<Transaction>,
 }
 
 impl Block {
-    pub fn new(id: BlockId, previous_hash: &Hash, data: String) -> Self {
-        let now = Utc::now().timestamp();
-
-        let (nonce, hash) = Block::mine_block(id, now, &previous_hash, &data);
-
-        Self {
-            header: Header::new(id, now, nonce, hash, previous_hash.clone()),
-            data,
-        }
-    }
-
-    pub fn genesis() -> Self {
-        Self {
-            header: Header::new(
-                0,
-                Utc::now().timestamp(),
-                0,
-                Hash::wrap(
-                    String::from("0000f816a87f806bb0073dcf026a64fb40c946b5abee2573702828694d5b4c43")
-                ),
-                Hash::wrap(String::from("GENESIS!"))
-            ),
-            data: String::from("GENESIS!"),
-        }
+    pub(crate) fn assemble(header: Header, data: Vec<Transaction>) -> Self {
+        Self { header, data }
     }
 
     pub fn is_genesis(&self) -> bool {
         self.header.id == 0
     }
 
-    pub fn is_valid(&self, previous_block: &Block) -> bool {
+    /// Chain-structure rules every block must satisfy regardless of consensus engine: it
+    /// links to its stated predecessor, ids increase by one, transactions are individually
+    /// valid and in nonce order, and the stored hash matches the block's actual content.
+    /// Consensus-specific rules (proof-of-work target, or whatever a different `Engine`
+    /// requires) are that engine's job, not this one's.
+    ///
+    /// `previous_blocks` holds every block of the chain up to (but not including) `self`,
+    /// oldest first, so an `Engine` can look further back than the immediate parent.
+    pub fn is_valid_structure(&self, previous_blocks: &[Block]) -> bool {
+        let previous_block = match previous_blocks.last() {
+            Some(block) => block,
+            None => {
+                return false;
+            }
+        };
+
         if self.header.previous_hash != previous_block.header.hash {
             return false;
         }
@@ -86,11 +81,11 @@ impl Block {
             }
         }
 
-        if !self.header.hash.unwrap().starts_with(DIFFICULTY) {
+        if self.header.id != previous_block.header.id + 1 {
             return false;
         }
 
-        if self.header.id != previous_block.header.id + 1 {
+        if !self.transactions_are_valid(previous_blocks) {
             return false;
         }
 
@@ -101,6 +96,36 @@ impl Block {
         true
     }
 
+    /// `previous_blocks` seeds the per-sender nonce watermark with every transaction already
+    /// committed to the chain so a previously-broadcast transaction can't be replayed into a
+    /// later block: a sender's nonce must strictly increase across the whole chain, not just
+    /// within `self.data`.
+    fn transactions_are_valid(&self, previous_blocks: &[Block]) -> bool {
+        let mut last_nonce_by_sender: HashMap<&[u8], Nonce> = HashMap::new();
+
+        for block in previous_blocks {
+            for transaction in &block.data {
+                last_nonce_by_sender.insert(&transaction.sender, transaction.nonce);
+            }
+        }
+
+        for transaction in &self.data {
+            if !transaction.is_valid() {
+                return false;
+            }
+
+            if let Some(&last_nonce) = last_nonce_by_sender.get(transaction.sender.as_slice()) {
+                if transaction.nonce <= last_nonce {
+                    return false;
+                }
+            }
+
+            last_nonce_by_sender.insert(&transaction.sender, transaction.nonce);
+        }
+
+        true
+    }
+
     pub fn regenerate_hash(&self) -> Hash {
         let data =
             serde_json::json!({
@@ -108,17 +133,22 @@ impl Block {
             "timestamp": self.header.timestamp,
             "nonce": self.header.nonce,
             "previous_hash": self.header.previous_hash,
+            "target": self.header.target,
             "data": self.data,
         });
 
         Hash::new(&data.to_string())
     }
 
+    /// `target` is folded into the hashed content so the hash itself attests to the difficulty
+    /// a miner claimed to meet, rather than relying solely on `Engine::verify_seal` re-deriving
+    /// and exact-matching the expected target out of band.
     pub fn calculate_hash(
         id: BlockId,
         timestamp: Timestamp,
         previous_hash: &Hash,
-        data: &str,
+        target: &Hash,
+        data: &[Transaction],
         nonce: Nonce
     ) -> Hash {
         let data =
@@ -126,6 +156,7 @@ impl Block {
             "id": id,
             "timestamp": timestamp,
             "previous_hash": previous_hash,
+            "target": target,
             "data": data,
             "nonce": nonce,
         });
@@ -136,28 +167,50 @@ impl Block {
     pub fn to_json_string(&self) -> Result<String, serde_json::Error> {
         serde_json::to_string(&self)
     }
+}
 
-    fn mine_block(
-        id: BlockId,
-        timestamp: Timestamp,
-        previous_hash: &Hash,
-        data: &str
-    ) -> (Nonce, Hash) {
-        info!("mining block...");
-        let mut nonce = 0;
-
-        loop {
-            if nonce % 100000 == 0 {
-                info!("nonce: {}", nonce);
-            }
-            let hash = Block::calculate_hash(id, timestamp, &previous_hash, data, nonce);
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-            if hash.matches_difficulty(DIFFICULTY) {
-                info!("mined! nonce: {}, hash: {}", nonce, hash);
-                return (nonce, hash);
-            }
+    fn block_with(id: BlockId, data: Vec<Transaction>) -> Block {
+        Block::assemble(
+            Header::new(
+                id,
+                0,
+                0,
+                Hash::wrap(String::from("h")),
+                Hash::wrap(String::from("p")),
+                Hash::wrap(String::from("t"))
+            ),
+            data
+        )
+    }
 
-            nonce += 1;
-        }
+    #[test]
+    fn transactions_are_valid_rejects_nonce_replayed_from_an_earlier_block() {
+        let transaction = Transaction::new(None, String::from("payload"), 0);
+        let earlier_block = block_with(1, vec![transaction.clone()]);
+        let replay_block = block_with(2, vec![transaction]);
+
+        assert!(!replay_block.transactions_are_valid(&[earlier_block]));
+    }
+
+    #[test]
+    fn transactions_are_valid_accepts_a_strictly_increasing_nonce() {
+        let first = Transaction::new(None, String::from("payload"), 0);
+        let earlier_block = block_with(1, vec![first]);
+        let second = Transaction::new(None, String::from("payload"), 1);
+        let next_block = block_with(2, vec![second]);
+
+        assert!(next_block.transactions_are_valid(&[earlier_block]));
+    }
+
+    #[test]
+    fn transactions_are_valid_rejects_non_increasing_nonce_within_the_same_block() {
+        let transaction = Transaction::new(None, String::from("payload"), 0);
+        let block = block_with(1, vec![transaction.clone(), transaction]);
+
+        assert!(!block.transactions_are_valid(&[]));
     }
 }
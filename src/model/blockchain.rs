@@ -1,32 +1,78 @@
 use super::block::Block;
+use crate::engine::Engine;
+use crate::storage::Storage;
+
+const DB_PATH: &str = "blockchain.db";
 
-#[derive(Clone)]
 pub struct Blockchain {
     pub blocks: Vec<Block>,
+    pub(crate) storage: Storage,
+    engine: Box<dyn Engine>,
 }
 
 impl Blockchain {
-    pub fn new() -> Self {
-        Self { blocks: vec![] }
+    pub fn new(engine: Box<dyn Engine>) -> Self {
+        let storage = Storage::open(DB_PATH);
+        let blocks = storage.load_all_blocks();
+
+        Self { blocks, storage, engine }
     }
 
-    pub fn genesis(&mut self) -> Self {
-        Blockchain {
-            blocks: vec![Block::genesis()],
-        }
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
     }
 
-    pub fn add_block(&mut self, block: Block) -> Result<bool, String> {
-        let latest_block = self.blocks.last().unwrap();
+    pub fn genesis(&mut self) {
+        let genesis_block = self.engine.genesis();
+
+        self.storage.insert_block(&genesis_block);
+        self.blocks.push(genesis_block);
+    }
+
+    /// Builds, seals (via the configured engine) and appends a new block carrying `data`.
+    pub fn mine_block(&mut self, data: Vec<super::transaction::Transaction>) -> Block {
+        let new_block = self.engine.seal(&self.blocks, data);
+
+        self.storage.insert_block(&new_block);
+        self.blocks.push(new_block.clone());
 
-        if !block.is_valid(&latest_block) {
+        new_block
+    }
+
+    pub fn add_block(&mut self, block: Block) -> Result<bool, String> {
+        if !block.is_valid_structure(&self.blocks) {
             return Err(String::from("Invalid block!"));
         }
 
+        self.engine.verify_seal(&block, &self.blocks)?;
+
+        self.storage.insert_block(&block);
         self.blocks.push(block);
         Ok(true)
     }
 
+    /// Exposes the engine's seal check so other modules (e.g. remote-chain verification) can
+    /// validate a block without reaching into `self.engine` directly.
+    pub(crate) fn verify_seal(&self, block: &Block, previous_blocks: &[Block]) -> Result<(), String> {
+        self.engine.verify_seal(block, previous_blocks)
+    }
+
+    /// Discards the local chain in favour of `blocks`, which the caller has already verified,
+    /// and rewrites the persisted chain to match.
+    ///
+    /// There is no partial sync: `ChainResponse` carries a peer's whole chain, and the local
+    /// chain is always replaced as a whole. `Blockchain` exposes no by-id or range query for
+    /// fetching only the blocks a node is missing.
+    pub fn replace_chain(&mut self, blocks: Vec<Block>) {
+        self.storage.clear();
+
+        for block in &blocks {
+            self.storage.insert_block(block);
+        }
+
+        self.blocks = blocks;
+    }
+
     pub fn is_chain_valid(&self) -> bool {
         self.blocks
             .iter()
@@ -36,30 +82,10 @@ impl Blockchain {
                     return true;
                 }
 
-                self.blocks[i - 1].is_valid(block)
-            })
-    }
+                let previous_blocks = &self.blocks[..i];
 
-    pub fn choose_chain(&self, local: Blockchain, remote: Blockchain) -> Blockchain {
-        let is_local_valid = self.is_chain_valid();
-        let is_remote_valid = self.is_chain_valid();
-
-        if !is_local_valid && !is_remote_valid {
-            panic!("Both chains are invalid!");
-        }
-
-        if is_local_valid && !is_remote_valid {
-            return local;
-        }
-
-        if !is_local_valid && is_remote_valid {
-            return remote;
-        }
-
-        if local.blocks.len() > remote.blocks.len() {
-            local
-        } else {
-            remote
-        }
+                block.is_valid_structure(previous_blocks) &&
+                    self.engine.verify_seal(block, previous_blocks).is_ok()
+            })
     }
 }
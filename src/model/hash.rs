@@ -1,9 +1,12 @@
 use std::fmt;
 
 use hex::{ self, FromHexError };
+use num_bigint::BigUint;
 use serde::{ Deserialize, Serialize };
 use sha2::{ Digest, Sha256 };
 
+const HASH_BYTE_LEN: usize = 32;
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Hash(pub String);
 
@@ -21,10 +24,6 @@ impl Hash {
         Self(hash)
     }
 
-    pub fn matches_difficulty(&self, difficulty: &str) -> bool {
-        self.0.starts_with(difficulty)
-    }
-
     pub fn unwrap(&self) -> String {
         self.0.clone()
     }
@@ -32,6 +31,31 @@ impl Hash {
     pub fn decode(&self) -> Result<Vec<u8>, FromHexError> {
         hex::decode(&self.0)
     }
+
+    /// Interprets this hash as a big-endian unsigned integer, for comparison against a
+    /// numeric mining target.
+    pub fn to_biguint(&self) -> BigUint {
+        let bytes = self.decode().unwrap_or_default();
+
+        BigUint::from_bytes_be(&bytes)
+    }
+
+    /// Encodes a numeric target back into a fixed-width (32 byte) hash representation.
+    pub fn from_biguint(value: &BigUint) -> Self {
+        let mut bytes = value.to_bytes_be();
+
+        if bytes.len() < HASH_BYTE_LEN {
+            let mut padded = vec![0u8; HASH_BYTE_LEN - bytes.len()];
+            padded.extend_from_slice(&bytes);
+            bytes = padded;
+        }
+
+        Self(hex::encode(bytes))
+    }
+
+    pub fn meets_target(&self, target: &BigUint) -> bool {
+        self.to_biguint() <= *target
+    }
 }
 
 impl fmt::Display for Hash {
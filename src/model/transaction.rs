@@ -0,0 +1,85 @@
+use libp2p::identity;
+use serde::{ Deserialize, Serialize };
+
+use super::block::Nonce;
+use crate::p2p::KEYS;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Transaction {
+    pub sender: Vec<u8>,
+    pub recipient: Option<Vec<u8>>,
+    pub payload: String,
+    pub nonce: Nonce,
+    pub signature: Vec<u8>,
+}
+
+impl Transaction {
+    pub fn new(recipient: Option<Vec<u8>>, payload: String, nonce: Nonce) -> Self {
+        let sender = KEYS.public().to_protobuf_encoding();
+        let message = Transaction::canonical_message(&sender, &recipient, &payload, nonce);
+        let signature = KEYS.sign(&message).expect("can sign transaction");
+
+        Self {
+            sender,
+            recipient,
+            payload,
+            nonce,
+            signature,
+        }
+    }
+
+    pub fn is_valid(&self) -> bool {
+        let public_key = match identity::PublicKey::from_protobuf_encoding(&self.sender) {
+            Ok(key) => key,
+            Err(_) => {
+                return false;
+            }
+        };
+
+        let message = Transaction::canonical_message(
+            &self.sender,
+            &self.recipient,
+            &self.payload,
+            self.nonce
+        );
+
+        public_key.verify(&message, &self.signature)
+    }
+
+    fn canonical_message(
+        sender: &[u8],
+        recipient: &Option<Vec<u8>>,
+        payload: &str,
+        nonce: Nonce
+    ) -> Vec<u8> {
+        let data =
+            serde_json::json!({
+            "sender": sender,
+            "recipient": recipient,
+            "payload": payload,
+            "nonce": nonce,
+        });
+
+        data.to_string().into_bytes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn is_valid_accepts_a_freshly_signed_transaction() {
+        let transaction = Transaction::new(None, String::from("payload"), 0);
+
+        assert!(transaction.is_valid());
+    }
+
+    #[test]
+    fn is_valid_rejects_a_tampered_payload() {
+        let mut transaction = Transaction::new(None, String::from("payload"), 0);
+        transaction.payload = String::from("tampered");
+
+        assert!(!transaction.is_valid());
+    }
+}
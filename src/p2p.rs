@@ -1,24 +1,69 @@
 use libp2p::{
-    floodsub::{ Floodsub, FloodsubEvent, FloodsubMessage, Topic },
+    gossipsub::{
+        Gossipsub,
+        GossipsubConfigBuilder,
+        GossipsubEvent,
+        GossipsubMessage,
+        IdentTopic as Topic,
+        MessageAuthenticity,
+        MessageId,
+        ValidationMode,
+    },
     identity,
     mdns::{ Mdns, MdnsEvent },
+    rendezvous,
     swarm::{ NetworkBehaviourEventProcess, Swarm },
+    Multiaddr,
     NetworkBehaviour,
     PeerId,
 };
-use log::{ error, info };
+use log::{ error, info, warn };
 use once_cell::sync::Lazy;
 use serde::{ Deserialize, Serialize };
+use std::collections::hash_map::DefaultHasher;
 use std::collections::HashSet;
+use std::hash::{ Hash, Hasher };
 use tokio::sync::mpsc;
 
-use crate::model::{ block::Block, blockchain::Blockchain };
+use crate::chain_verify::{ self, BlockQuality };
+use crate::model::{ block::Block, blockchain::Blockchain, transaction::Transaction };
 
 pub static KEYS: Lazy<identity::Keypair> = Lazy::new(identity::Keypair::generate_ed25519);
 pub static PEER_ID: Lazy<PeerId> = Lazy::new(|| PeerId::from(KEYS.public()));
 pub static CHAIN_TOPIC: Lazy<Topic> = Lazy::new(|| Topic::new("CHAINS"));
 pub static BLOCK_TOPIC: Lazy<Topic> = Lazy::new(|| Topic::new("BLOCKS"));
 
+/// Every gossipsub message on either topic is one of these, serialized once and matched on
+/// explicitly — no more speculatively trying to deserialize the payload as each known shape
+/// in turn.
+#[derive(Debug, Serialize, Deserialize)]
+pub enum WireMessage {
+    Request(LocalChainRequest),
+    ChainResponse(ChainResponse),
+    Block(Block),
+}
+
+impl WireMessage {
+    pub(crate) fn to_bytes(&self) -> Vec<u8> {
+        serde_json::to_vec(self).expect("can serialize wire message")
+    }
+}
+
+/// Content-addressed message id: two nodes that receive the same bytes via different paths
+/// (mesh flooding, re-gossip) compute the same id, so gossipsub dedups them instead of
+/// reprocessing the same block or chain response repeatedly.
+fn message_id(message: &GossipsubMessage) -> MessageId {
+    let mut hasher = DefaultHasher::new();
+
+    message.data.hash(&mut hasher);
+
+    MessageId::from(hasher.finish().to_string())
+}
+
+/// Namespace nodes register under with a rendezvous point, so discovery queries only ever
+/// surface peers of this chain rather than every client of a shared rendezvous server.
+pub const RENDEZVOUS_NAMESPACE: &str = "CHAINS";
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ChainResponse {
     pub blocks: Vec<Block>,
@@ -38,8 +83,10 @@ pub enum EventType {
 
 #[derive(NetworkBehaviour)]
 pub struct BlockchainBehaviour {
-    pub floodsub: Floodsub,
+    pub gossipsub: Gossipsub,
     pub mdns: Mdns,
+    pub rendezvous_client: rendezvous::client::Behaviour,
+    pub rendezvous_server: rendezvous::server::Behaviour,
 
     #[behaviour(ignore)]
     pub response_sender: mpsc::UnboundedSender<ChainResponse>,
@@ -49,41 +96,112 @@ pub struct BlockchainBehaviour {
 
     #[behaviour(ignore)]
     pub blockchain: Blockchain,
+
+    /// The known rendezvous point this node registers with and queries for peers. `None` if
+    /// this node *is* the rendezvous point, or if no rendezvous point was configured.
+    #[behaviour(ignore)]
+    pub rendezvous_point: Option<(PeerId, Multiaddr)>,
+
+    /// Peers learned from the rendezvous point, kept separate from mdns's LAN-only set.
+    #[behaviour(ignore)]
+    pub rendezvous_peers: HashSet<PeerId>,
 }
 
 impl BlockchainBehaviour {
     pub async fn new(
         blockchain: Blockchain,
         response_sender: mpsc::UnboundedSender<ChainResponse>,
-        init_sender: mpsc::UnboundedSender<bool>
+        init_sender: mpsc::UnboundedSender<bool>,
+        rendezvous_point: Option<(PeerId, Multiaddr)>
     ) -> Self {
+        let gossipsub_config = GossipsubConfigBuilder::default()
+            .validation_mode(ValidationMode::Strict)
+            .message_id_fn(message_id)
+            .build()
+            .expect("can build gossipsub config");
+
         let mut behaviour = Self {
             blockchain,
-            floodsub: Floodsub::new(*PEER_ID),
+            gossipsub: Gossipsub::new(MessageAuthenticity::Signed(KEYS.clone()), gossipsub_config)
+                .expect("can create gossipsub behaviour"),
             mdns: Mdns::new(Default::default()).await.expect("can create mdns"),
+            rendezvous_client: rendezvous::client::Behaviour::new(KEYS.clone()),
+            rendezvous_server: rendezvous::server::Behaviour::new(
+                rendezvous::server::Config::default()
+            ),
             response_sender,
             init_sender,
+            rendezvous_point,
+            rendezvous_peers: HashSet::new(),
         };
 
-        behaviour.floodsub.subscribe(CHAIN_TOPIC.clone());
-        behaviour.floodsub.subscribe(BLOCK_TOPIC.clone());
+        behaviour.gossipsub.subscribe(&CHAIN_TOPIC).expect("can subscribe to chain topic");
+        behaviour.gossipsub.subscribe(&BLOCK_TOPIC).expect("can subscribe to block topic");
 
         behaviour
     }
 }
 
+impl NetworkBehaviourEventProcess<rendezvous::client::Event> for BlockchainBehaviour {
+    fn inject_event(&mut self, event: rendezvous::client::Event) {
+        match event {
+            rendezvous::client::Event::Registered { namespace, .. } => {
+                info!("Registered with rendezvous point under '{}'", namespace);
+            }
+            rendezvous::client::Event::RegisterFailed(error) => {
+                warn!("Failed to register with rendezvous point: {:?}", error);
+            }
+            rendezvous::client::Event::Discovered { registrations, .. } => {
+                for registration in registrations {
+                    let peer_id = registration.record.peer_id();
+
+                    if peer_id == *PEER_ID {
+                        continue;
+                    }
+
+                    self.rendezvous_peers.insert(peer_id);
+                    self.gossipsub.add_explicit_peer(&peer_id);
+                }
+
+                info!("Discovered {} peer(s) via rendezvous", self.rendezvous_peers.len());
+            }
+            rendezvous::client::Event::DiscoverFailed { error, .. } => {
+                warn!("Rendezvous discovery failed: {:?}", error);
+            }
+            _ => (),
+        }
+    }
+}
+
+impl NetworkBehaviourEventProcess<rendezvous::server::Event> for BlockchainBehaviour {
+    fn inject_event(&mut self, event: rendezvous::server::Event) {
+        match event {
+            rendezvous::server::Event::PeerRegistered { peer, registration } => {
+                info!("Rendezvous: registered {} under '{}'", peer, registration.namespace);
+            }
+            rendezvous::server::Event::PeerNotRegistered { peer, .. } => {
+                warn!("Rendezvous: {} tried to discover without registering", peer);
+            }
+            rendezvous::server::Event::DiscoverServed { enquirer, .. } => {
+                info!("Rendezvous: served discovery request to {}", enquirer);
+            }
+            _ => (),
+        }
+    }
+}
+
 impl NetworkBehaviourEventProcess<MdnsEvent> for BlockchainBehaviour {
     fn inject_event(&mut self, event: MdnsEvent) {
         match event {
             MdnsEvent::Discovered(discovered_list) => {
                 discovered_list.into_iter().for_each(|(peer_id, _addr)| {
-                    self.floodsub.add_node_to_partial_view(peer_id);
+                    self.gossipsub.add_explicit_peer(&peer_id);
                 });
             }
             MdnsEvent::Expired(expired_list) => {
                 expired_list.into_iter().for_each(|(peer_id, _addr)| {
                     if !self.mdns.has_node(&peer_id) {
-                        self.floodsub.remove_node_from_partial_view(&peer_id);
+                        self.gossipsub.remove_explicit_peer(&peer_id);
                     }
                 });
             }
@@ -91,26 +209,34 @@ impl NetworkBehaviourEventProcess<MdnsEvent> for BlockchainBehaviour {
     }
 }
 
-fn handle_chain_response(msg: &FloodsubMessage, resp: ChainResponse, blockchain: &mut Blockchain) {
-    info!("Response from: {}", msg.source);
+fn handle_chain_response(source: &PeerId, resp: ChainResponse, blockchain: &mut Blockchain) {
+    info!("Response from: {}", source);
 
-    resp.blocks.iter().for_each(|r| info!("{:?}", r));
-
-    let remote_blockchain = Blockchain { blocks: resp.blocks };
-
-    *blockchain = blockchain.choose_chain(blockchain.clone(), remote_blockchain);
+    match chain_verify::classify_remote_chain(blockchain, &resp.blocks) {
+        BlockQuality::Good => {
+            if chain_verify::should_replace(&blockchain.blocks, &resp.blocks) {
+                info!("Adopting remote chain ({} blocks)", resp.blocks.len());
+                blockchain.replace_chain(resp.blocks);
+            } else {
+                info!("Remote chain is valid but not better than ours, keeping local chain");
+            }
+        }
+        BlockQuality::Bad(reason) => error!("Rejected remote chain: {}", reason),
+        BlockQuality::Future => error!("Rejected remote chain: a block is timestamped too far in the future"),
+        BlockQuality::Fork => error!("Rejected remote chain: diverges from local history"),
+    }
 }
 
 fn handle_local_chain_request(
-    msg: &FloodsubMessage,
+    source: &PeerId,
     blockchain: &Blockchain,
     response_sender: &mut mpsc::UnboundedSender<ChainResponse>
 ) {
-    info!("Sending local chain to: {}", msg.source.to_string());
+    info!("Sending local chain to: {}", source);
 
     let send_result = response_sender.send(ChainResponse {
         blocks: blockchain.blocks.clone(),
-        receiver: msg.source.to_string(),
+        receiver: source.to_string(),
     });
 
     match send_result {
@@ -128,21 +254,30 @@ fn handle_received_block(block: Block, blockchain: &mut Blockchain) {
     }
 }
 
-impl NetworkBehaviourEventProcess<FloodsubEvent> for BlockchainBehaviour {
-    fn inject_event(&mut self, event: FloodsubEvent) {
-        if let FloodsubEvent::Message(msg) = event {
-            if let Ok(resp) = serde_json::from_slice::<ChainResponse>(&msg.data) {
-                if resp.receiver == PEER_ID.to_string() {
-                    handle_chain_response(&msg, resp, &mut self.blockchain);
+impl NetworkBehaviourEventProcess<GossipsubEvent> for BlockchainBehaviour {
+    fn inject_event(&mut self, event: GossipsubEvent) {
+        if let GossipsubEvent::Message { propagation_source, message, .. } = event {
+            let wire_message = match serde_json::from_slice::<WireMessage>(&message.data) {
+                Ok(wire_message) => wire_message,
+                Err(e) => {
+                    warn!("Dropping unreadable gossipsub message: {}", e);
+                    return;
                 }
-            }
-
-            if let Ok(_) = serde_json::from_slice::<LocalChainRequest>(&msg.data) {
-                handle_local_chain_request(&msg, &self.blockchain, &mut self.response_sender);
-            }
+            };
 
-            if let Ok(block) = serde_json::from_slice::<Block>(&msg.data) {
-                handle_received_block(block, &mut self.blockchain);
+            match wire_message {
+                WireMessage::ChainResponse(resp) if resp.receiver == PEER_ID.to_string() => {
+                    handle_chain_response(&propagation_source, resp, &mut self.blockchain);
+                }
+                WireMessage::ChainResponse(_) => (),
+                WireMessage::Request(_) => {
+                    handle_local_chain_request(
+                        &propagation_source,
+                        &self.blockchain,
+                        &mut self.response_sender
+                    );
+                }
+                WireMessage::Block(block) => handle_received_block(block, &mut self.blockchain),
             }
         }
     }
@@ -151,12 +286,9 @@ impl NetworkBehaviourEventProcess<FloodsubEvent> for BlockchainBehaviour {
 pub fn get_list_of_peers(swarm: &Swarm<BlockchainBehaviour>) -> Vec<String> {
     info!("Discovered peers:");
 
-    let nodes = swarm.behaviour().mdns.discovered_nodes();
-    let mut unique_peers = HashSet::new();
+    let mut unique_peers: HashSet<PeerId> = swarm.behaviour().mdns.discovered_nodes().copied().collect();
 
-    nodes.into_iter().for_each(|peer| {
-        unique_peers.insert(peer);
-    });
+    unique_peers.extend(swarm.behaviour().rendezvous_peers.iter().copied());
 
     unique_peers
         .iter()
@@ -170,6 +302,71 @@ pub fn handle_print_peers(swarm: &Swarm<BlockchainBehaviour>) {
         .for_each(|peer| info!("{}", peer));
 }
 
+/// Dials this node's configured rendezvous point. A no-op if the node wasn't started with
+/// `--rendezvous <multiaddr>`. Registration itself happens once the connection actually comes
+/// up (see `register_on_connection`) rather than right after dialing, since `dial` only queues
+/// the connection attempt.
+pub fn dial_rendezvous_point(swarm: &mut Swarm<BlockchainBehaviour>) {
+    let rendezvous_point = swarm.behaviour().rendezvous_point.clone();
+
+    if let Some((_, rendezvous_addr)) = rendezvous_point {
+        if let Err(e) = Swarm::dial(swarm, rendezvous_addr) {
+            warn!("Could not dial rendezvous point: {:?}", e);
+        }
+    }
+}
+
+/// Registers with the rendezvous point once a connection to it is actually established.
+/// Firing `register` right after `Swarm::dial` risks the registration being silently dropped
+/// since dialing only queues the attempt, it doesn't wait for the handshake to complete.
+pub fn register_on_connection(swarm: &mut Swarm<BlockchainBehaviour>, peer_id: PeerId) {
+    let rendezvous_point = swarm.behaviour().rendezvous_point.clone();
+
+    if let Some((rendezvous_peer_id, _)) = rendezvous_point {
+        if peer_id != rendezvous_peer_id {
+            return;
+        }
+
+        swarm
+            .behaviour_mut()
+            .rendezvous_client.register(
+                rendezvous::Namespace::from_static(RENDEZVOUS_NAMESPACE),
+                rendezvous_peer_id,
+                None
+            );
+    }
+}
+
+/// Triggers a discovery round against the configured rendezvous point. Results arrive later
+/// as `rendezvous::client::Event::Discovered` and are folded into `rendezvous_peers`.
+pub fn discover_via_rendezvous(swarm: &mut Swarm<BlockchainBehaviour>) {
+    let rendezvous_point = swarm.behaviour().rendezvous_point.clone();
+
+    if let Some((rendezvous_peer_id, _)) = rendezvous_point {
+        swarm
+            .behaviour_mut()
+            .rendezvous_client.discover(
+                Some(rendezvous::Namespace::from_static(RENDEZVOUS_NAMESPACE)),
+                None,
+                None,
+                rendezvous_peer_id
+            );
+    } else {
+        info!("No rendezvous point configured, nothing to discover");
+    }
+}
+
+/// Handles the `list peers` stdin command: kicks off a fresh discovery round and prints
+/// every peer known so far, mDNS and rendezvous alike.
+pub fn handle_list_rendezvous_peers(swarm: &mut Swarm<BlockchainBehaviour>) {
+    discover_via_rendezvous(swarm);
+
+    info!("Known peers (mdns + rendezvous):");
+    get_list_of_peers(swarm)
+        .iter()
+        .for_each(|peer| info!("{}", peer));
+}
+
 pub fn handle_print_chain(swarm: &Swarm<BlockchainBehaviour>) {
     info!("Local blockchain:");
 
@@ -180,25 +377,34 @@ pub fn handle_print_chain(swarm: &Swarm<BlockchainBehaviour>) {
     info!("{}", pretty_json);
 }
 
+fn next_transaction_nonce(blockchain: &Blockchain) -> u64 {
+    let own_sender = KEYS.public().to_protobuf_encoding();
+
+    blockchain.blocks
+        .iter()
+        .flat_map(|block| &block.data)
+        .filter(|transaction| transaction.sender == own_sender)
+        .map(|transaction| transaction.nonce + 1)
+        .max()
+        .unwrap_or(0)
+}
+
 pub fn handle_create_block(cmd: &str, swarm: &mut Swarm<BlockchainBehaviour>) {
     cmd.strip_prefix("create block").and_then(|data| {
         let behaviour = swarm.behaviour_mut();
 
-        let latest_block = behaviour.blockchain.blocks.last().unwrap();
-
-        let new_block = Block::new(
-            latest_block.header.id + 1,
-            &latest_block.header.hash,
-            data.to_owned()
-        );
+        let nonce = next_transaction_nonce(&behaviour.blockchain);
+        let transaction = Transaction::new(None, data.trim().to_owned(), nonce);
 
-        let stringified_new_block = new_block.to_json_string().expect("can stringify block");
-
-        behaviour.blockchain.blocks.push(new_block);
+        let new_block = behaviour.blockchain.mine_block(vec![transaction]);
+        let wire_message = WireMessage::Block(new_block);
 
         info!("Broadcasting block to peers...");
 
-        behaviour.floodsub.publish(BLOCK_TOPIC.clone(), stringified_new_block.as_bytes());
+        match behaviour.gossipsub.publish(BLOCK_TOPIC.clone(), wire_message.to_bytes()) {
+            Ok(_) => info!("Block published"),
+            Err(e) => error!("Failed to publish block: {:?}", e),
+        }
 
         Some(())
     });
@@ -0,0 +1,115 @@
+use rusqlite::{ params, Connection, Row };
+
+use crate::model::block::{ Block, BlockId, Header };
+use crate::model::hash::Hash;
+use crate::model::transaction::Transaction;
+
+#[derive(Clone)]
+pub struct Storage {
+    path: String,
+}
+
+impl Storage {
+    pub fn open(path: &str) -> Self {
+        let storage = Self { path: path.to_owned() };
+
+        storage.init_db();
+        storage
+    }
+
+    fn connection(&self) -> Connection {
+        Connection::open(&self.path).expect("can open blockchain.db")
+    }
+
+    fn init_db(&self) {
+        self.connection()
+            .execute(
+                "CREATE TABLE IF NOT EXISTS blocks (
+                    id INTEGER PRIMARY KEY,
+                    timestamp INTEGER NOT NULL,
+                    nonce INTEGER NOT NULL,
+                    hash TEXT NOT NULL,
+                    previous_hash TEXT NOT NULL,
+                    target TEXT NOT NULL,
+                    data TEXT NOT NULL
+                )",
+                []
+            )
+            .expect("can create blocks table");
+
+        self.connection()
+            .execute("CREATE INDEX IF NOT EXISTS idx_blocks_id ON blocks (id)", [])
+            .expect("can create blocks id index");
+    }
+
+    pub fn insert_block(&self, block: &Block) {
+        let data = serde_json::to_string(&block.data).expect("can serialize block data");
+
+        self.connection()
+            .execute(
+                "INSERT INTO blocks (id, timestamp, nonce, hash, previous_hash, target, data)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![
+                    block.header.id as i64,
+                    block.header.timestamp,
+                    block.header.nonce as i64,
+                    block.header.hash.unwrap(),
+                    block.header.previous_hash.unwrap(),
+                    block.header.target.unwrap(),
+                    data
+                ]
+            )
+            .expect("can persist block");
+    }
+
+    fn get_blocks_from(&self, from_id: BlockId) -> Vec<Block> {
+        let connection = self.connection();
+        let mut statement = connection
+            .prepare(
+                "SELECT id, timestamp, nonce, hash, previous_hash, target, data
+                 FROM blocks WHERE id >= ?1 ORDER BY id ASC"
+            )
+            .expect("can prepare range query");
+
+        statement
+            .query_map(params![from_id as i64], Storage::row_to_block)
+            .expect("can query blocks range")
+            .filter_map(Result::ok)
+            .collect()
+    }
+
+    pub fn load_all_blocks(&self) -> Vec<Block> {
+        self.get_blocks_from(0)
+    }
+
+    /// Wipes all persisted blocks, used when a better remote chain replaces the local one.
+    pub fn clear(&self) {
+        self.connection().execute("DELETE FROM blocks", []).expect("can clear blocks table");
+    }
+
+    fn row_to_block(row: &Row) -> rusqlite::Result<Block> {
+        let id: i64 = row.get(0)?;
+        let timestamp = row.get(1)?;
+        let nonce: i64 = row.get(2)?;
+        let hash: String = row.get(3)?;
+        let previous_hash: String = row.get(4)?;
+        let target: String = row.get(5)?;
+        let data: String = row.get(6)?;
+
+        let transactions: Vec<Transaction> = serde_json
+            ::from_str(&data)
+            .expect("can deserialize stored block data");
+
+        Ok(Block {
+            header: Header {
+                id: id as BlockId,
+                timestamp,
+                nonce: nonce as u64,
+                hash: Hash::wrap(hash),
+                previous_hash: Hash::wrap(previous_hash),
+                target: Hash::wrap(target),
+            },
+            data: transactions,
+        })
+    }
+}